@@ -0,0 +1,71 @@
+use std::time::Duration;
+use rodio::{Device, Sink, Source};
+
+// An infinite square wave at `freq` Hz, the classic CHIP-8 buzzer timbre.
+pub struct SquareWave {
+    freq: f32,
+    sample_rate: u32,
+    sample_idx: u32,
+}
+
+impl SquareWave {
+    fn new(freq: f32, sample_rate: u32) -> SquareWave {
+        SquareWave { freq, sample_rate, sample_idx: 0 }
+    }
+}
+
+impl Iterator for SquareWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let samples_per_half_period = (self.sample_rate as f32 / (2.0 * self.freq)) as u32;
+        let value = if (self.sample_idx / samples_per_half_period) % 2 == 0 { 0.25 } else { -0.25 };
+        self.sample_idx = self.sample_idx.wrapping_add(1);
+        Some(value)
+    }
+}
+
+impl Source for SquareWave {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+const SAMPLE_RATE: u32 = 44100;
+
+// Drives a continuous tone on/off to track the CHIP-8 sound timer: starts the
+// instant it becomes nonzero, stops exactly when it hits zero, rather than
+// layering a fixed-length beep on top of every frame it's active.
+pub struct Buzzer {
+    sink: Sink,
+    tone_hz: f32,
+    playing: bool,
+}
+
+impl Buzzer {
+    pub fn new(device: &Device, tone_hz: f32) -> Buzzer {
+        Buzzer { sink: Sink::new(device), tone_hz, playing: false }
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        if active && !self.playing {
+            self.sink.append(SquareWave::new(self.tone_hz, SAMPLE_RATE));
+            self.playing = true;
+        } else if !active && self.playing {
+            self.sink.stop();
+            self.playing = false;
+        }
+    }
+}
@@ -1,3 +1,7 @@
+mod display;
+mod keypad;
+mod timer;
+
 use std::io::prelude::*;
 use std::fs::File;
 use std::io::BufReader;
@@ -5,8 +9,52 @@ use rand::{Rng, thread_rng};
 use rand::rngs::ThreadRng;
 use rand::distributions::Uniform;
 
-pub const WIDTH: usize = 64;
-pub const HEIGHT: usize = 32;
+pub use display::{Display, LORES_WIDTH, LORES_HEIGHT, HIRES_WIDTH, HIRES_HEIGHT};
+pub use keypad::Keypad;
+use timer::Timer;
+
+const FONT_ADDR: usize = 0x050;
+const BIG_FONT_ADDR: usize = 0x0A0;
+
+const SAVE_STATE_MAGIC: &[u8; 4] = b"C8ST";
+const SAVE_STATE_VERSION: u8 = 2;
+
+// Toggles for opcode behaviors that differ between real CHIP-8 interpreters.
+// Different ROMs were authored against different interpreters and only run
+// correctly under the matching set of quirks.
+#[derive(Clone, Copy)]
+pub struct Quirks {
+    // 8XY1/8XY2/8XY3 zero VF after the operation
+    pub vf_reset: bool,
+    // 8XY6/8XYE shift VX in place; when false, VY is copied into VX first
+    pub shift: bool,
+    // FX55/FX65 increment I by X+1 afterwards
+    pub load_store: bool,
+    // BNNN is interpreted as BXNN, jumping to XNN + VX instead of NNN + V0
+    pub jump: bool,
+    // DXYN clips sprites at the screen edge instead of wrapping them with modulo
+    pub clipping: bool,
+}
+
+impl Quirks {
+    pub fn chip8() -> Quirks {
+        Quirks { vf_reset: true, shift: false, load_store: true, jump: false, clipping: true }
+    }
+
+    pub fn schip() -> Quirks {
+        Quirks { vf_reset: false, shift: true, load_store: false, jump: true, clipping: true }
+    }
+
+    pub fn xochip() -> Quirks {
+        Quirks { vf_reset: false, shift: false, load_store: true, jump: false, clipping: false }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::schip()
+    }
+}
 
 const CHIP8_FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -27,6 +75,20 @@ const CHIP8_FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+// SUPER-CHIP large font: 10 bytes per digit, digits 0-9 only.
+const CHIP8_BIG_FONTSET: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x30, 0x30, 0x30, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
 pub struct Chip8 {
     // Memory and CPU stuff
     memory: [u8; 4096],
@@ -34,39 +96,52 @@ pub struct Chip8 {
     v: [u8; 16], // General purpose V registers
     i: usize, // Index register
     pc: usize, // Program Counter
-    delay_timer: u8,
-    sound_timer: u8,
+    delay_timer: Timer,
+    sound_timer: Timer,
 
     // Needed for CXNNN
     rng: ThreadRng,
 
-    // Store pressed key values here
-    pub keys: [u8; 16],
+    pub keypad: Keypad,
+    pub display: Display,
+
+    // Set true by 00E0 and DXYN; the caller should redraw and then clear this
+    pub request_redraw: bool,
 
-    // Display
-    pub screen: [usize; WIDTH*HEIGHT],
+    // RPL user flags, written/read by FX75/FX85
+    rpl: [u8; 8],
+
+    // Set by 00FD; tells the caller the program asked to stop running
+    pub halted: bool,
+
+    quirks: Quirks,
 }
 
 enum PCUpdateFlag {
     Next,
     Skip,
     Block,
+    Halt,
     Set(usize),
 }
 
 impl Chip8 {
-    pub fn new() -> Chip8 {
+    pub fn new(quirks: Quirks) -> Chip8 {
         Chip8 {
-            keys: [0; 16],
             memory: [0; 4096],
             stack: vec![],
             v: [0; 16],
             i: 0,
             pc: 0x200,
-            delay_timer: 0,
-            sound_timer: 0,
-            screen: [0; WIDTH*HEIGHT],
+            delay_timer: Timer::new(),
+            sound_timer: Timer::new(),
+            keypad: Keypad::new(),
+            display: Display::new(),
+            request_redraw: false,
+            rpl: [0; 8],
+            halted: false,
             rng: thread_rng(),
+            quirks,
         }
     }
 
@@ -82,7 +157,10 @@ impl Chip8 {
 
     fn load_font(&mut self) {
         for (i, v) in CHIP8_FONTSET.iter().enumerate() {
-            self.memory[0x050+i] = *v
+            self.memory[FONT_ADDR+i] = *v
+        }
+        for (i, v) in CHIP8_BIG_FONTSET.iter().enumerate() {
+            self.memory[BIG_FONT_ADDR+i] = *v
         }
     }
 
@@ -93,18 +171,17 @@ impl Chip8 {
             PCUpdateFlag::Skip => self.pc += 4,
             PCUpdateFlag::Set(addr) => self.pc = addr,
             PCUpdateFlag::Block => (),
+            PCUpdateFlag::Halt => (),
 
         }
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-        }
-        // TODO: Implement actual buzzer
-        if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                println!("BEEP");
-            }
-            self.sound_timer -= 1;
-        }
+    }
+
+    // Decrements the delay and sound timers. The CHIP-8 spec ticks both at a
+    // fixed 60Hz, independent of the CPU clock, so callers should invoke this
+    // once per display frame rather than once per `run_cycle`.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer.tick();
+        self.sound_timer.tick();
     }
 
     fn fetch_opcode(&self) -> u16 {
@@ -113,19 +190,58 @@ impl Chip8 {
 
     fn execute_opcode(&mut self, op: u16) -> PCUpdateFlag {
         match op & 0xF000 {
-            // 00E0 and 00EE
-            0x0000 => match op & 0x00FF {
-                // 00E0: Clears the screen
-                0xE0 => {
-                    self.screen = [0; WIDTH*HEIGHT];
-                    PCUpdateFlag::Next
+            // Multiple 0x0000 opcodes
+            0x0000 => {
+                // 00CN: Scrolls the display down N pixels
+                if op & 0x00F0 == 0x00C0 {
+                    let n = (op & 0x000F) as usize;
+                    self.display.scroll_down(n);
+                    self.request_redraw = true;
+                    return PCUpdateFlag::Next
+                }
+                match op & 0x00FF {
+                    // 00E0: Clears the screen
+                    0xE0 => {
+                        self.display.clear();
+                        self.request_redraw = true;
+                        PCUpdateFlag::Next
+                    }
+                    // 00EE: Returns from subroutine
+                    0xEE => {
+                        self.pc = self.stack.pop().expect("0x0EE opcode ran with an empty stack!");
+                        PCUpdateFlag::Next
+                    },
+                    // 00FB: Scrolls the display right 4 pixels
+                    0xFB => {
+                        self.display.scroll_right(4);
+                        self.request_redraw = true;
+                        PCUpdateFlag::Next
+                    },
+                    // 00FC: Scrolls the display left 4 pixels
+                    0xFC => {
+                        self.display.scroll_left(4);
+                        self.request_redraw = true;
+                        PCUpdateFlag::Next
+                    },
+                    // 00FD: Halts the interpreter
+                    0xFD => {
+                        self.halted = true;
+                        PCUpdateFlag::Halt
+                    },
+                    // 00FE: Switches to 64x32 low-resolution mode
+                    0xFE => {
+                        self.display.set_hires(false);
+                        self.request_redraw = true;
+                        PCUpdateFlag::Next
+                    },
+                    // 00FF: Switches to 128x64 high-resolution mode
+                    0xFF => {
+                        self.display.set_hires(true);
+                        self.request_redraw = true;
+                        PCUpdateFlag::Next
+                    },
+                    _ => panic!("Unknown 0x00E opcode: {:x}", op)
                 }
-                // 00EE: Returns from subroutine
-                0xEE => {
-                    self.pc = self.stack.pop().expect("0x0EE opcode ran with an empty stack!");
-                    PCUpdateFlag::Next
-                },
-                _ => panic!("Unknown 0x00E opcode: {:x}", op)
             }
             // 1NNN: Jump to address NNN
             0x1000 => PCUpdateFlag::Set(get_addr(op)),
@@ -204,11 +320,13 @@ impl Chip8 {
                     self.v[pvx] = res;
                     PCUpdateFlag::Next
                 },
-                // 8XY6: Stores the least significant bit of VX in VF and then shifts VX right by 1
+                // 8XY6: Stores the least significant bit of VX (or VY, per the shift quirk) in
+                // VF and then shifts the result right by 1, storing it in VX
                 0x6 => {
-                    let pvx = get_opx(op);
-                    self.v[0xF] = self.v[pvx] & 0x1;
-                    self.v[pvx] >>= 1;
+                    let (pvx, pvy) = get_opxy(op);
+                    let src = if self.quirks.shift { self.v[pvx] } else { self.v[pvy] };
+                    self.v[0xF] = src & 0x1;
+                    self.v[pvx] = src >> 1;
                     PCUpdateFlag::Next
                 },
                 // 8XY7: Sets VX = VY - VX (Set carry flag)
@@ -223,11 +341,13 @@ impl Chip8 {
                     self.v[pvx] = res;
                     PCUpdateFlag::Next
                 },
-                // 8XYE: Stores the least significant bit of VX in VF and then shifts VX left by 1
+                // 8XYE: Stores the most significant bit of VX (or VY, per the shift quirk) in
+                // VF and then shifts the result left by 1, storing it in VX
                 0xE => {
-                    let pvx = get_opx(op);
-                    self.v[0xF] = self.v[pvx] & 0x1;
-                    self.v[pvx] <<= 1;
+                    let (pvx, pvy) = get_opxy(op);
+                    let src = if self.quirks.shift { self.v[pvx] } else { self.v[pvy] };
+                    self.v[0xF] = (src & 0x80) >> 7;
+                    self.v[pvx] = src << 1;
                     PCUpdateFlag::Next
                 },
                 _ => panic!("Unknown 0x8000 opcode: {:x}", op)
@@ -241,11 +361,13 @@ impl Chip8 {
                 self.i = get_addr(op);
                 PCUpdateFlag::Next
             },
-            // BNNN: Jumps to the address NNN plus V0
+            // BNNN: Jumps to the address NNN plus V0 (or, per the jump quirk, BXNN jumps to
+            // XNN plus VX)
             0xB000 => {
                 let nnn = get_addr(op);
-                let v0 = self.v[0] as usize;
-                PCUpdateFlag::Set(nnn+v0)
+                let reg = if self.quirks.jump { get_opx(op) } else { 0 };
+                let offset = self.v[reg] as usize;
+                PCUpdateFlag::Set(nnn+offset)
             }
             // CXNN: Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
             0xC000 => {
@@ -256,36 +378,28 @@ impl Chip8 {
                 self.v[pvx] = (num & nn) as u8;
                 PCUpdateFlag::Next
             }
-            // DXYN: Draws a sprite at VX,VY, 8px wide, height of N+1px. Each row read from
-            // memory[I]. Set VF to 1 if any pixel goes from 1 to 0, set to 0 if that doesn't
-            // happen.
+            // DXYN: Draws a sprite at VX,VY, 8px wide, height of N px, reading
+            // rows from memory[I]. DXY0 in high-res mode instead draws a
+            // 16x16 sprite (2 bytes per row). Set VF to 1 if any pixel goes
+            // from 1 to 0 (in high-res mode, the number of rows that had a
+            // collision), 0 otherwise.
             0xD000 => {
                 let vx = self.v[get_opx(op)] as usize;
                 let vy = self.v[get_opy(op)] as usize;
                 let n = (op & 0x000F) as usize;
 
-                self.v[0xF] = 0;
-                for y in 0..n {
-                    let px = self.memory[self.i+y];
-                    for x in 0..8 {
-                        let location = vx + x + ((vy + y) * WIDTH);
-                        if px & (0x80 >> x) != 0 {
-                            if self.screen[location] == 1 {
-                                self.v[0xF] = 1;
-                            }
-                            self.screen[location] ^= 1;
-                        }
-                    }
+                if self.display.is_hires() && n == 0 {
+                    self.draw_sprite(vx, vy, 16, 16)
+                } else {
+                    self.draw_sprite(vx, vy, 8, n)
                 }
-
-                PCUpdateFlag::Next
             },
             // Multiple 0xE000 opcodes
             0xE000 => match op & 0x00FF {
                 // EX9E: Skips next instruction if key in VX is pressed
                 0x9E => {
                     let vx = self.v[get_opx(op)] as usize;
-                    if self.keys[vx] == 1 {
+                    if self.keypad.is_pressed(vx) {
                         PCUpdateFlag::Skip
                     }
                     else {
@@ -295,7 +409,7 @@ impl Chip8 {
                 // EXA1: Skips next instruction if key in VX ISN'T pressed
                 0xA1 => {
                     let vx = self.v[get_opx(op)] as usize;
-                    if self.keys[vx] != 1 {
+                    if !self.keypad.is_pressed(vx) {
                         PCUpdateFlag::Skip
                     }
                     else {
@@ -308,28 +422,26 @@ impl Chip8 {
             0xF000 => match op & 0x00FF {
                 // FX07: Sets VX to the value of the delay timer
                 0x07 => {
-                    self.v[get_opx(op)] = self.delay_timer;
+                    self.v[get_opx(op)] = self.delay_timer.get();
                     PCUpdateFlag::Next
                 }
                 // FX0A: A key press is awaited and then stored in VX (blocking)
                 0x0A => {
                     let vx = get_opx(op);
-                    for i in 0..16 {
-                        if self.keys[i] == 1 {
-                            self.v[vx] = i as u8;
-                            return PCUpdateFlag::Next
-                        }
+                    if let Some(key) = self.keypad.pressed_key() {
+                        self.v[vx] = key as u8;
+                        return PCUpdateFlag::Next
                     }
                     PCUpdateFlag::Block
                 },
                 // FX15: Set delay timer to VX
                 0x15 => {
-                    self.delay_timer = self.v[get_opx(op)];
+                    self.delay_timer.set(self.v[get_opx(op)]);
                     PCUpdateFlag::Next
                 },
                 // FX18: Set sound timer to VX
                 0x18 => {
-                    self.sound_timer = self.v[get_opx(op)];
+                    self.sound_timer.set(self.v[get_opx(op)]);
                     PCUpdateFlag::Next
                 },
                 // FX1E: Adds VX to I (carry flag not set)
@@ -342,30 +454,62 @@ impl Chip8 {
                     self.i = (self.v[get_opx(op)]+0x050) as usize;
                     PCUpdateFlag::Next
                 },
+                // FX30: Sets I to the location of the large (10-byte) sprite for the digit in VX
+                0x30 => {
+                    self.i = BIG_FONT_ADDR + (self.v[get_opx(op)] as usize) * 10;
+                    PCUpdateFlag::Next
+                },
                 // FX33: Stores the BCD representatin of VX, with the most significant of three
                 // digits at the address in I, the middle digit at I+1, and the least significat
                 // digit at I+2
                 0x33 => {
                     let mut vx = self.v[get_opx(op)];
                     for i in (0..3).rev() {
-                        self.memory[self.i + i] = vx % 10;
+                        let addr = self.wrapped_addr(i);
+                        self.memory[addr] = vx % 10;
                         vx /= 10;
                     }
                     PCUpdateFlag::Next
                 },
-                // FX55: Stores V0 to VX (inclusive) in memory addr starting at I
+                // FX55: Stores V0 to VX (inclusive) in memory addr starting at I. Per the
+                // load-store quirk, I is then left advanced by X+1.
                 0x55 => {
                     let vx = get_opx(op);
                     for i in 0..=vx {
-                        self.memory[self.i+i] = self.v[i]
+                        let addr = self.wrapped_addr(i);
+                        self.memory[addr] = self.v[i]
+                    }
+                    if self.quirks.load_store {
+                        self.i += vx + 1;
                     }
                     PCUpdateFlag::Next
                 },
-                // FX55: Loads V0 to VX (inclusive) in memory addr starting at I
+                // FX55: Loads V0 to VX (inclusive) in memory addr starting at I. Per the
+                // load-store quirk, I is then left advanced by X+1.
                 0x65 => {
                     let vx = get_opx(op);
                     for i in 0..=vx {
-                        self.v[i] = self.memory[self.i+i]
+                        let addr = self.wrapped_addr(i);
+                        self.v[i] = self.memory[addr]
+                    }
+                    if self.quirks.load_store {
+                        self.i += vx + 1;
+                    }
+                    PCUpdateFlag::Next
+                },
+                // FX75: Stores V0 to VX (inclusive, up to V7) into the RPL user flags
+                0x75 => {
+                    let vx = get_opx(op);
+                    for i in 0..=vx.min(7) {
+                        self.rpl[i] = self.v[i];
+                    }
+                    PCUpdateFlag::Next
+                },
+                // FX85: Loads V0 to VX (inclusive, up to V7) from the RPL user flags
+                0x85 => {
+                    let vx = get_opx(op);
+                    for i in 0..=vx.min(7) {
+                        self.v[i] = self.rpl[i];
                     }
                     PCUpdateFlag::Next
                 },
@@ -375,6 +519,29 @@ impl Chip8 {
         }
     }
 
+    // Draws a `width`x`height` sprite (width a multiple of 8) at (vx, vy).
+    // Shared by DXYN and the SCHIP DXY0 16x16 form.
+    // I can be left pointing anywhere in memory (e.g. by FX1E, or by repeated
+    // FX55/FX65 with the load-store quirk advancing it), so every memory access
+    // relative to I wraps modulo memory size rather than indexing straight off
+    // the end.
+    fn wrapped_addr(&self, offset: usize) -> usize {
+        (self.i + offset) % self.memory.len()
+    }
+
+    fn draw_sprite(&mut self, vx: usize, vy: usize, width: usize, height: usize) -> PCUpdateFlag {
+        let bytes_per_row = width / 8;
+        let mut rows = vec![0u8; height * bytes_per_row];
+        for (offset, byte) in rows.iter_mut().enumerate() {
+            *byte = self.memory[self.wrapped_addr(offset)];
+        }
+        let collided_rows = self.display.draw(vx, vy, width, &rows, self.quirks.clipping);
+
+        self.v[0xF] = if self.display.is_hires() { collided_rows } else { (collided_rows > 0) as u8 };
+        self.request_redraw = true;
+        PCUpdateFlag::Next
+    }
+
     fn cond_skip_v(&self, op: u16, f: impl Fn(u8, u8) -> bool) -> PCUpdateFlag {
         let vx = self.v[get_opx(op)];
         let vy = self.v[get_opy(op)];
@@ -400,8 +567,109 @@ impl Chip8 {
     fn set_vx(&mut self, op: u16, f: impl Fn(u8, u8) -> u8) -> PCUpdateFlag {
         let (pvx, pvy) = get_opxy(op);
         self.v[pvx] = f(self.v[pvx], self.v[pvy]);
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         PCUpdateFlag::Next
     }
+
+    // Serializes the full machine state (memory, registers, timers, keys and
+    // screen) into a versioned binary blob. The RNG isn't serializable and is
+    // deliberately left out; `load_state` re-seeds a fresh one.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(SAVE_STATE_MAGIC);
+        buf.push(SAVE_STATE_VERSION);
+
+        buf.extend_from_slice(&self.memory);
+        buf.extend_from_slice(&self.v);
+        buf.extend_from_slice(&(self.i as u16).to_le_bytes());
+        buf.extend_from_slice(&(self.pc as u16).to_le_bytes());
+        buf.push(self.delay_timer.get());
+        buf.push(self.sound_timer.get());
+        buf.extend_from_slice(&self.rpl);
+        buf.push(self.halted as u8);
+
+        buf.extend_from_slice(&(self.stack.len() as u16).to_le_bytes());
+        for addr in &self.stack {
+            buf.extend_from_slice(&(*addr as u16).to_le_bytes());
+        }
+
+        buf.extend_from_slice(&self.keypad.snapshot());
+        buf.extend_from_slice(&self.display.snapshot());
+
+        buf
+    }
+
+    // Restores state previously produced by `save_state`.
+    pub fn load_state(&mut self, data: &[u8]) {
+        assert_eq!(&data[0..4], SAVE_STATE_MAGIC, "not a chipurat8 save state");
+        assert_eq!(data[4], SAVE_STATE_VERSION, "unsupported save state version");
+        let mut pos = 5;
+
+        self.memory.copy_from_slice(&data[pos..pos+4096]);
+        pos += 4096;
+        self.v.copy_from_slice(&data[pos..pos+16]);
+        pos += 16;
+        self.i = u16::from_le_bytes([data[pos], data[pos+1]]) as usize;
+        pos += 2;
+        self.pc = u16::from_le_bytes([data[pos], data[pos+1]]) as usize;
+        pos += 2;
+        self.delay_timer.set(data[pos]);
+        pos += 1;
+        self.sound_timer.set(data[pos]);
+        pos += 1;
+        self.rpl.copy_from_slice(&data[pos..pos+8]);
+        pos += 8;
+        self.halted = data[pos] != 0;
+        pos += 1;
+
+        let stack_len = u16::from_le_bytes([data[pos], data[pos+1]]) as usize;
+        pos += 2;
+        self.stack.clear();
+        for _ in 0..stack_len {
+            self.stack.push(u16::from_le_bytes([data[pos], data[pos+1]]) as usize);
+            pos += 2;
+        }
+
+        let mut keys = [0u8; 16];
+        keys.copy_from_slice(&data[pos..pos+16]);
+        self.keypad.restore(keys);
+        pos += 16;
+
+        self.display.restore(&data[pos..]);
+
+        self.rng = thread_rng();
+        self.request_redraw = true;
+    }
+
+    // Inspection helpers for the debugger in main.rs
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn index(&self) -> usize {
+        self.i
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn call_stack(&self) -> &[usize] {
+        &self.stack
+    }
+
+    // The opcode at `pc`, without executing it
+    pub fn peek_opcode(&self) -> u16 {
+        self.fetch_opcode()
+    }
+
+    // Whether the sound timer is currently nonzero, i.e. the buzzer should be sounding
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer.is_active()
+    }
 }
 
 fn get_addr(op: u16) -> usize {
@@ -420,3 +688,132 @@ fn get_opxy(op: u16) -> (usize, usize) {
     (get_opx(op), get_opy(op))
 }
 
+// Names an opcode for the debugger, using the same decoders `execute_opcode` uses.
+pub fn disassemble(op: u16) -> String {
+    match op & 0xF000 {
+        0x0000 => {
+            if op & 0x00F0 == 0x00C0 {
+                format!("SCD {:#X}", op & 0x000F)
+            } else {
+                match op & 0x00FF {
+                    0xE0 => "CLS".to_string(),
+                    0xEE => "RET".to_string(),
+                    0xFB => "SCR".to_string(),
+                    0xFC => "SCL".to_string(),
+                    0xFD => "EXIT".to_string(),
+                    0xFE => "LOW".to_string(),
+                    0xFF => "HIGH".to_string(),
+                    _ => format!("DATA {:#06X}", op),
+                }
+            }
+        },
+        0x1000 => format!("JP {:#05X}", get_addr(op)),
+        0x2000 => format!("CALL {:#05X}", get_addr(op)),
+        0x3000 => format!("SE V{:X}, {:#04X}", get_opx(op), op & 0x00FF),
+        0x4000 => format!("SNE V{:X}, {:#04X}", get_opx(op), op & 0x00FF),
+        0x5000 => format!("SE V{:X}, V{:X}", get_opx(op), get_opy(op)),
+        0x6000 => format!("LD V{:X}, {:#04X}", get_opx(op), op & 0x00FF),
+        0x7000 => format!("ADD V{:X}, {:#04X}", get_opx(op), op & 0x00FF),
+        0x8000 => {
+            let (x, y) = get_opxy(op);
+            match op & 0x000F {
+                0x0 => format!("LD V{:X}, V{:X}", x, y),
+                0x1 => format!("OR V{:X}, V{:X}", x, y),
+                0x2 => format!("AND V{:X}, V{:X}", x, y),
+                0x3 => format!("XOR V{:X}, V{:X}", x, y),
+                0x4 => format!("ADD V{:X}, V{:X}", x, y),
+                0x5 => format!("SUB V{:X}, V{:X}", x, y),
+                0x6 => format!("SHR V{:X}, V{:X}", x, y),
+                0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+                0xE => format!("SHL V{:X}, V{:X}", x, y),
+                _ => format!("DATA {:#06X}", op),
+            }
+        },
+        0x9000 => format!("SNE V{:X}, V{:X}", get_opx(op), get_opy(op)),
+        0xA000 => format!("LD I, {:#05X}", get_addr(op)),
+        0xB000 => format!("JP V0, {:#05X}", get_addr(op)),
+        0xC000 => format!("RND V{:X}, {:#04X}", get_opx(op), op & 0x00FF),
+        0xD000 => format!("DRW V{:X}, V{:X}, {:#X}", get_opx(op), get_opy(op), op & 0x000F),
+        0xE000 => match op & 0x00FF {
+            0x9E => format!("SKP V{:X}", get_opx(op)),
+            0xA1 => format!("SKNP V{:X}", get_opx(op)),
+            _ => format!("DATA {:#06X}", op),
+        },
+        0xF000 => {
+            let x = get_opx(op);
+            match op & 0x00FF {
+                0x07 => format!("LD V{:X}, DT", x),
+                0x0A => format!("LD V{:X}, K", x),
+                0x15 => format!("LD DT, V{:X}", x),
+                0x18 => format!("LD ST, V{:X}", x),
+                0x1E => format!("ADD I, V{:X}", x),
+                0x29 => format!("LD F, V{:X}", x),
+                0x30 => format!("LD HF, V{:X}", x),
+                0x33 => format!("LD B, V{:X}", x),
+                0x55 => format!("LD [I], V{:X}", x),
+                0x65 => format!("LD V{:X}, [I]", x),
+                0x75 => format!("LD R, V{:X}", x),
+                0x85 => format!("LD V{:X}, R", x),
+                _ => format!("DATA {:#06X}", op),
+            }
+        },
+        _ => format!("DATA {:#06X}", op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_state_round_trips_through_load_state() {
+        let mut chip8 = Chip8::new(Quirks::schip());
+        chip8.memory[0x300] = 0xAB;
+        chip8.v[3] = 0x42;
+        chip8.i = 0x310;
+        chip8.pc = 0x204;
+        chip8.delay_timer.set(10);
+        chip8.sound_timer.set(20);
+        chip8.rpl[2] = 7;
+        chip8.halted = true;
+        chip8.stack.push(0x200);
+        chip8.stack.push(0x400);
+        chip8.keypad.press(5);
+        chip8.display.set_hires(true);
+        chip8.display.screen[42] = 1;
+
+        let snapshot = chip8.save_state();
+
+        let mut restored = Chip8::new(Quirks::schip());
+        restored.load_state(&snapshot);
+
+        assert_eq!(restored.memory[0x300], 0xAB);
+        assert_eq!(restored.v[3], 0x42);
+        assert_eq!(restored.i, 0x310);
+        assert_eq!(restored.pc, 0x204);
+        assert_eq!(restored.delay_timer.get(), 10);
+        assert_eq!(restored.sound_timer.get(), 20);
+        assert_eq!(restored.rpl[2], 7);
+        assert!(restored.halted);
+        assert_eq!(restored.stack, vec![0x200, 0x400]);
+        assert!(restored.keypad.is_pressed(5));
+        assert!(restored.display.is_hires());
+        assert_eq!(restored.display.screen[42], 1);
+        assert!(restored.request_redraw);
+    }
+
+    #[test]
+    fn save_state_round_trips_a_deep_stack() {
+        let mut chip8 = Chip8::new(Quirks::schip());
+        for addr in 0..300 {
+            chip8.stack.push(addr);
+        }
+
+        let snapshot = chip8.save_state();
+
+        let mut restored = Chip8::new(Quirks::schip());
+        restored.load_state(&snapshot);
+
+        assert_eq!(restored.stack, chip8.stack);
+    }
+}
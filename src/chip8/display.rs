@@ -0,0 +1,138 @@
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+// Owns the screen buffer and the low/high-resolution switch, and knows how to
+// XOR sprites and scroll the visible area onto it.
+pub struct Display {
+    // Always sized for the high-resolution display; `hires` controls how much
+    // of it is active and how opcodes address it.
+    pub screen: [usize; HIRES_WIDTH*HIRES_HEIGHT],
+    hires: bool,
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            screen: [0; HIRES_WIDTH*HIRES_HEIGHT],
+            hires: false,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.screen = [0; HIRES_WIDTH*HIRES_HEIGHT];
+    }
+
+    // XORs a sprite of `width` pixels wide (a multiple of 8) onto the screen at
+    // (vx, vy), reading `width/8` bytes per row from `rows`. Returns the number
+    // of rows that had a collision.
+    //
+    // The sprite's origin always wraps modulo the screen dimensions, per the
+    // original COSMAC VIP behavior. Once drawing starts, individual pixels that
+    // fall past the edge are clipped (dropped) if `clipping` is set, or wrapped
+    // to the other side otherwise.
+    pub fn draw(&mut self, vx: usize, vy: usize, width: usize, rows: &[u8], clipping: bool) -> u8 {
+        let bytes_per_row = width / 8;
+        let screen_width = self.width();
+        let screen_height = self.height();
+        let ox = vx % screen_width;
+        let oy = vy % screen_height;
+        let mut collided_rows = 0u8;
+
+        for (y, row) in rows.chunks_exact(bytes_per_row).enumerate() {
+            let mut row_collided = false;
+            let raw_sy = oy + y;
+            if clipping && raw_sy >= screen_height {
+                continue;
+            }
+            let sy = raw_sy % screen_height;
+
+            for x in 0..width {
+                let byte = row[x / 8];
+                if byte & (0x80 >> (x % 8)) == 0 {
+                    continue;
+                }
+                let raw_sx = ox + x;
+                if clipping && raw_sx >= screen_width {
+                    continue;
+                }
+                let sx = raw_sx % screen_width;
+
+                let location = sx + sy * screen_width;
+                if self.screen[location] == 1 {
+                    row_collided = true;
+                }
+                self.screen[location] ^= 1;
+            }
+            if row_collided {
+                collided_rows += 1;
+            }
+        }
+
+        collided_rows
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        let w = self.width();
+        let h = self.height();
+        for y in (0..h).rev() {
+            for x in 0..w {
+                self.screen[x + y*w] = if y >= n { self.screen[x + (y-n)*w] } else { 0 };
+            }
+        }
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        let w = self.width();
+        let h = self.height();
+        for y in 0..h {
+            for x in (0..w).rev() {
+                self.screen[x + y*w] = if x >= n { self.screen[(x-n) + y*w] } else { 0 };
+            }
+        }
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        let w = self.width();
+        let h = self.height();
+        for y in 0..h {
+            for x in 0..w {
+                self.screen[x + y*w] = if x+n < w { self.screen[(x+n) + y*w] } else { 0 };
+            }
+        }
+    }
+
+    // Serializes the hires flag and the full screen buffer (one byte per pixel).
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(1 + self.screen.len());
+        buf.push(self.hires as u8);
+        buf.extend(self.screen.iter().map(|&pixel| pixel as u8));
+        buf
+    }
+
+    // Restores a snapshot produced by `snapshot`.
+    pub fn restore(&mut self, data: &[u8]) {
+        self.hires = data[0] != 0;
+        for (pixel, byte) in self.screen.iter_mut().zip(&data[1..]) {
+            *pixel = *byte as usize;
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// The 16-key CHIP-8 hex keypad, keyed 0x0-0xF.
+pub struct Keypad {
+    keys: [u8; 16],
+}
+
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad { keys: [0; 16] }
+    }
+
+    pub fn press(&mut self, key: usize) {
+        self.keys[key] = 1;
+    }
+
+    pub fn release(&mut self, key: usize) {
+        self.keys[key] = 0;
+    }
+
+    pub fn is_pressed(&self, key: usize) -> bool {
+        self.keys[key] == 1
+    }
+
+    // The lowest-numbered key currently held down, if any.
+    pub fn pressed_key(&self) -> Option<usize> {
+        (0..16).find(|&i| self.is_pressed(i))
+    }
+
+    pub fn snapshot(&self) -> [u8; 16] {
+        self.keys
+    }
+
+    pub fn restore(&mut self, keys: [u8; 16]) {
+        self.keys = keys;
+    }
+}
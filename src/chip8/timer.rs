@@ -0,0 +1,29 @@
+// A simple down-counter, ticked once per cycle. Used for both the delay timer
+// and the sound timer, which only differ in what the CPU does while nonzero.
+pub struct Timer {
+    value: u8,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer { value: 0 }
+    }
+
+    pub fn get(&self) -> u8 {
+        self.value
+    }
+
+    pub fn set(&mut self, value: u8) {
+        self.value = value;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.value > 0
+    }
+
+    pub fn tick(&mut self) {
+        if self.value > 0 {
+            self.value -= 1;
+        }
+    }
+}
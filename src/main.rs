@@ -6,9 +6,11 @@ use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 use clap::{Arg, App, crate_version};
-use rodio::{Sink, Source};
 use std::time::{Duration, Instant};
-use chipurat8::chip8::{Chip8, WIDTH, HEIGHT};
+use chipurat8::chip8::{self, Chip8, Quirks, HIRES_WIDTH, HIRES_HEIGHT};
+
+mod buzzer;
+use buzzer::Buzzer;
 
 const KEY_MAP: [(VirtualKeyCode, usize); 16] = [
     (VirtualKeyCode::Key1, 0x1),
@@ -30,6 +32,13 @@ const KEY_MAP: [(VirtualKeyCode, usize); 16] = [
 ];
 
 
+fn print_debug_step(chip8: &Chip8, pc: usize, op: u16) {
+    println!("{:#06X}: {:#06X}  {}", pc, op, chip8::disassemble(op));
+    println!("  v: {:02X?}", chip8.registers());
+    println!("  i: {:#06X}", chip8.index());
+    println!("  stack: {:02X?}", chip8.call_stack());
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = App::new("Chipurat8")
         .version(crate_version!())
@@ -47,21 +56,58 @@ fn main() -> Result<(), Box<dyn Error>> {
             .about("HZ to set the CPU to operate at")
             .takes_value(true)
             .default_value("500"))
+        .arg(Arg::new("quirks")
+            .short('q')
+            .long("quirks")
+            .about("Compatibility profile to interpret ambiguous opcodes with")
+            .takes_value(true)
+            .possible_values(&["chip8", "schip", "xochip"])
+            .default_value("schip"))
+        .arg(Arg::new("debug")
+            .short('d')
+            .long("debug")
+            .about("Pause execution and single-step one cycle at a time with Space"))
+        .arg(Arg::new("break")
+            .long("break")
+            .about("Run at full speed until PC reaches this address, then start single-stepping")
+            .takes_value(true))
+        .arg(Arg::new("tone-hz")
+            .long("tone-hz")
+            .about("Frequency, in Hz, of the square-wave buzzer tone")
+            .takes_value(true)
+            .default_value("440"))
         .get_matches();
 
     let rom = matches.value_of("rom").unwrap();
+    let state_path = format!("{}.state", rom);
     let cpu_hz = matches.value_of("cpu-hz").unwrap().parse::<f64>()?;
+    let quirks = match matches.value_of("quirks").unwrap() {
+        "chip8" => Quirks::chip8(),
+        "schip" => Quirks::schip(),
+        "xochip" => Quirks::xochip(),
+        _ => unreachable!(),
+    };
+    let break_addr = match matches.value_of("break") {
+        Some(addr) => Some(usize::from_str_radix(addr.trim_start_matches("0x"), 16)?),
+        None => None,
+    };
+    // --break is only meaningful with the stepping debugger, so it implies --debug.
+    let debugging = matches.is_present("debug") || break_addr.is_some();
+    let mut paused = debugging && break_addr.is_none();
+    let tone_hz = matches.value_of("tone-hz").unwrap().parse::<f32>()?;
 
     let cycles_per_frame = (cpu_hz / 60.0) as u64;
     let extra_cycle_every = (((cpu_hz / 60.0) % 1.0) * 10.0) as u64;
 
-    let mut chip8 = Chip8::new();
+    let mut chip8 = Chip8::new(quirks);
     chip8.init(rom);
 
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
     let window = {
-        let size = LogicalSize::new((WIDTH*4) as f64, (HEIGHT*4) as f64);
+        // Size the window for the high-resolution SCHIP display; low-resolution
+        // ROMs are scaled up 2x to fill the same pixel buffer.
+        let size = LogicalSize::new((HIRES_WIDTH*4) as f64, (HIRES_HEIGHT*4) as f64);
         WindowBuilder::new()
             .with_title("Chipurat8")
             .with_inner_size(size)
@@ -72,14 +118,14 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        PixelsBuilder::new(WIDTH as u32, HEIGHT as u32, surface_texture)
+        PixelsBuilder::new(HIRES_WIDTH as u32, HIRES_HEIGHT as u32, surface_texture)
             .enable_vsync(true)
             .build()?
     };
 
     // Set up some stuff for the sound
     let device = rodio::default_output_device().unwrap();
-    let sink = Sink::new(&device);
+    let mut buzzer = Buzzer::new(&device, tone_hz);
 
     // Control the timing of our updates
     let mut time = Instant::now();
@@ -87,6 +133,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let update_rate = Duration::from_micros(16667);
 
     let mut frame_count = 0;
+    let mut halt_reported = false;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -99,10 +146,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         if input.update(&event) {
             for (k, n) in KEY_MAP.iter() {
                 if input.key_pressed(*k) {
-                    chip8.keys[*n] = 1
+                    chip8.keypad.press(*n)
                 }
                 if input.key_released(*k) {
-                    chip8.keys[*n] = 0
+                    chip8.keypad.release(*n)
                 }
             }
 
@@ -111,47 +158,108 @@ fn main() -> Result<(), Box<dyn Error>> {
                 return;
             }
 
+            if input.key_pressed(VirtualKeyCode::F5) {
+                std::fs::write(&state_path, chip8.save_state()).expect("Could not write save state");
+            }
+
+            if input.key_pressed(VirtualKeyCode::F9) {
+                if let Ok(data) = std::fs::read(&state_path) {
+                    chip8.load_state(&data);
+                    halt_reported = chip8.halted;
+                }
+            }
+
             if let Some(size) = input.window_resized() {
                 pixels.resize(size.width, size.height);
             }
+
+            if debugging && paused && input.key_pressed(VirtualKeyCode::Space) {
+                let pc = chip8.pc();
+                let op = chip8.peek_opcode();
+                chip8.run_cycle();
+                print_debug_step(&chip8, pc, op);
+            }
         }
 
         match event {
             Event::MainEventsCleared => {
                 if update_dt >= update_rate {
+                    if !chip8.halted {
+                        if !debugging {
+                            for _ in 0..cycles_per_frame {
+                                chip8.run_cycle();
+                                if chip8.halted {
+                                    break;
+                                }
+                            }
 
-                    for _ in 0..cycles_per_frame {
-                        chip8.run_cycle();
+                            // run an extra cycle every few frames to catch up to our target hz
+                            // if our target hz is not evenly divisble by 60
+                            if !chip8.halted && frame_count >= extra_cycle_every && extra_cycle_every != 0 {
+                                chip8.run_cycle();
+                                frame_count = 0;
+                            }
+
+                            if extra_cycle_every > 0 {
+                                frame_count += 1;
+                            }
+                        } else if !paused {
+                            // Run at full speed until the breakpoint is hit, then
+                            // drop into single-stepping via Space
+                            for _ in 0..cycles_per_frame {
+                                chip8.run_cycle();
+                                if chip8.halted {
+                                    paused = true;
+                                    break;
+                                }
+                                if Some(chip8.pc()) == break_addr {
+                                    paused = true;
+                                    println!("Breakpoint hit at {:#06X}", chip8.pc());
+                                    break;
+                                }
+                            }
+                        }
+                    }
+
+                    if chip8.halted && !halt_reported {
+                        println!("Chip8 halted at {:#06X} (00FD) — execution stopped", chip8.pc());
+                        halt_reported = true;
                     }
 
-                    // run an extra cycle every few frames to catch up to our target hz
-                    // if our target hz is not evenly divisble by 60
-                    if frame_count >= extra_cycle_every && extra_cycle_every != 0 {
-                        chip8.run_cycle();
-                        frame_count = 0;
+                    // Timers run at a fixed 60Hz, independent of cpu_hz, so they
+                    // tick once per frame here rather than once per run_cycle.
+                    // Skipped while the debugger holds execution paused, so a
+                    // breakpoint freezes the whole machine state, not just the PC.
+                    if !chip8.halted && (!debugging || !paused) {
+                        chip8.tick_timers();
                     }
+                    buzzer.set_active(chip8.sound_active());
+
+                    update_dt -= update_rate;
+                }
 
+                // The pixel buffer is always sized for the high-resolution
+                // display; low-resolution ROMs are scaled up 2x so they still
+                // fill the window. Only touch the GPU when something actually
+                // changed this cycle.
+                if chip8.request_redraw {
                     for (i, pixel) in pixels.get_frame().chunks_exact_mut(4).enumerate() {
-                        if chip8.screen[i] == 1 {
+                        let px = i % HIRES_WIDTH;
+                        let py = i / HIRES_WIDTH;
+                        let lit = if chip8.display.is_hires() {
+                            chip8.display.screen[px + py * chip8.display.width()] == 1
+                        } else {
+                            chip8.display.screen[(px/2) + (py/2) * chip8.display.width()] == 1
+                        };
+                        if lit {
                             pixel.copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF])
                         } else {
                             pixel.copy_from_slice(&[0x00, 0x00, 0x00, 0xFF])
                         }
                     }
-
-                    if extra_cycle_every > 0 {
-                        frame_count += 1;
-                    }
-
-                    chip8.dec_timers();
-                    if chip8.play_sound() {
-                        let sine = rodio::source::SineWave::new(440);
-                        sink.append(sine.take_duration(Duration::from_millis(50)));
-                    }
-
-                    update_dt -= update_rate;
+                    chip8.request_redraw = false;
+                    pixels.render().unwrap();
                 }
-                pixels.render().unwrap();
             },
             _ => (),
         };